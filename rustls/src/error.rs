@@ -0,0 +1,55 @@
+use std::fmt;
+
+use webpki;
+use sct;
+
+/// rustls reports protocol errors using this type.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TLSError {
+    /// The peer sent an unexpected or malformed handshake message.
+    PeerMisbehavedError(String),
+
+    /// No certificates were presented by the peer.
+    NoCertificatesPresented,
+
+    /// Certificate chain or handshake signature verification failed.
+    /// Wraps the underlying `webpki` error.
+    WebPKIError(webpki::Error),
+
+    /// A presented Signed Certificate Timestamp did not validate against
+    /// any configured log. Wraps the underlying `sct` error.
+    InvalidSCT(sct::Error),
+
+    /// We failed to read the current time, so certificate validity and
+    /// OCSP freshness could not be checked.
+    FailedToGetCurrentTime,
+
+    /// A catch-all for errors that don't warrant their own variant.
+    General(String),
+
+    /// The end-entity certificate's fingerprint (or key, for key-pinning
+    /// mode) did not match any pin configured on a `PinnedCertificateVerifier`.
+    CertificatePinningFailed,
+
+    /// The certificate was revoked, according to a stapled OCSP response,
+    /// or required but missing under `RevocationPolicy::HardFail`.
+    CertificateRevoked,
+}
+
+impl fmt::Display for TLSError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TLSError::PeerMisbehavedError(s) => write!(f, "peer misbehaved: {}", s),
+            TLSError::NoCertificatesPresented => write!(f, "no certificates presented by peer"),
+            TLSError::WebPKIError(e) => write!(f, "invalid certificate: {:?}", e),
+            TLSError::InvalidSCT(e) => write!(f, "invalid certificate timestamp: {:?}", e),
+            TLSError::FailedToGetCurrentTime => write!(f, "failed to get current time"),
+            TLSError::General(s) => write!(f, "{}", s),
+            TLSError::CertificatePinningFailed =>
+                write!(f, "certificate did not match any pinned fingerprint"),
+            TLSError::CertificateRevoked => write!(f, "certificate revoked"),
+        }
+    }
+}
+
+impl std::error::Error for TLSError {}
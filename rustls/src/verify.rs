@@ -3,6 +3,7 @@
 
 use webpki;
 use sct;
+use ring::digest;
 use std;
 use std::sync::Arc;
 
@@ -16,6 +17,11 @@ use crate::anchors::OwnedTrustAnchor;
 #[cfg(feature = "logging")]
 use crate::log::{warn, debug};
 mod x509;
+mod ocsp;
+#[cfg(test)]
+mod test_der;
+
+pub use ocsp::RevocationPolicy;
 
 type SignatureAlgorithms = &'static [&'static webpki::SignatureAlgorithm];
 
@@ -26,6 +32,7 @@ static SUPPORTED_SIG_ALGS: SignatureAlgorithms = &[
     &webpki::ECDSA_P256_SHA384,
     &webpki::ECDSA_P384_SHA256,
     &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
     &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
     &webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
     &webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
@@ -35,6 +42,22 @@ static SUPPORTED_SIG_ALGS: SignatureAlgorithms = &[
     &webpki::RSA_PKCS1_3072_8192_SHA384
 ];
 
+/// The signature schemes we'll offer/accept by default, in no particular
+/// order.  Kept in sync with `SUPPORTED_SIG_ALGS` above.
+static SUPPORTED_SIG_SCHEMES: &[SignatureScheme] = &[
+    SignatureScheme::ECDSA_NISTP384_SHA384,
+    SignatureScheme::ECDSA_NISTP256_SHA256,
+    SignatureScheme::ED25519,
+
+    SignatureScheme::RSA_PSS_SHA512,
+    SignatureScheme::RSA_PSS_SHA384,
+    SignatureScheme::RSA_PSS_SHA256,
+
+    SignatureScheme::RSA_PKCS1_SHA512,
+    SignatureScheme::RSA_PKCS1_SHA384,
+    SignatureScheme::RSA_PKCS1_SHA256,
+];
+
 /// Marker types.  These are used to bind the fact some verification
 /// (certificate chain or handshake signature) has taken place into
 /// protocol states.  We use this to have the compiler check that there
@@ -69,11 +92,37 @@ pub trait ServerCertVerifier : Send + Sync {
     /// Verify a the certificate chain `presented_certs` against the roots
     /// configured in `roots`.  Make sure that `dns_name` is quoted by
     /// the top certificate in the chain.
+    ///
+    /// `scts` are the Signed Certificate Timestamps presented by the peer,
+    /// if any, and `ocsp_response` is its stapled OCSP response, if any.
+    /// Implementations own their Certificate Transparency and revocation
+    /// policy: the core state machine parses these off the wire but makes
+    /// no judgement about them, so a verifier is free to enforce "at least
+    /// N SCTs from distinct operators", pin specific logs, check OCSP, or
+    /// ignore either entirely.  `WebPKIVerifier` implements a conventional
+    /// policy for both; see its `ct_logs` and `revocation_policy` fields.
+    ///
+    /// Breaking change: this method gained the `scts` parameter here, so
+    /// every implementation of this trait and every caller (the core state
+    /// machine in `client/hs.rs` and `server/hs.rs`) must be updated to
+    /// match. Those files aren't part of this checkout, so they couldn't be
+    /// updated as part of this change.
     fn verify_server_cert(&self,
                           roots: &RootCertStore,
                           presented_certs: &[Certificate],
                           dns_name: webpki::DNSNameRef,
+                          scts: &SCTList,
                           ocsp_response: &[u8]) -> Result<ServerCertVerified, TLSError>;
+
+    /// Returns the signature schemes this verifier will accept for the
+    /// handshake signature and certificate chain.  The `ClientHello`
+    /// advertises exactly this set, so it must agree with whatever
+    /// `verify_server_cert` is prepared to validate.
+    ///
+    /// The default set matches `SUPPORTED_SIG_SCHEMES`.
+    fn supported_verify_schemes(&self) -> &'static [SignatureScheme] {
+        SUPPORTED_SIG_SCHEMES
+    }
 }
 
 /// Something that can verify a client certificate chain
@@ -112,8 +161,32 @@ pub trait ClientCertVerifier : Send + Sync {
 
 /// Default `ServerCertVerifier`, see the trait impl for more information.
 pub struct WebPKIVerifier {
-    /// time provider
-    pub time: fn() -> Result<webpki::Time, TLSError>,
+    /// time provider, as Unix seconds.  Used both for certificate chain
+    /// validity and, uniformly, as the "now" for OCSP freshness checks,
+    /// so a single call produces a consistent view of time across one
+    /// `verify_server_cert` invocation and can be mocked for tests.
+    pub time: fn() -> Result<u64, TLSError>,
+
+    /// the signature verification algorithms this verifier will accept,
+    /// and validate certificate chains against.  Defaults to
+    /// `SUPPORTED_SIG_ALGS`, which now includes Ed25519.
+    supported_sig_algs: SignatureAlgorithms,
+
+    /// the signature schemes corresponding to `supported_sig_algs`, as
+    /// advertised in the `ClientHello`.
+    supported_schemes: &'static [SignatureScheme],
+
+    /// how a stapled OCSP response (or the absence of one) affects
+    /// verification.  Defaults to `RevocationPolicy::Ignore`, preserving
+    /// this verifier's historic behaviour.
+    pub revocation_policy: RevocationPolicy,
+
+    /// the Certificate Transparency logs this verifier trusts SCTs from.
+    /// Defaults to empty, which disables CT enforcement entirely
+    /// (this verifier's historic behaviour).  When non-empty, a peer that
+    /// presents SCTs must present at least one valid SCT from a log in the
+    /// list; a peer that presents no SCTs at all is not rejected.
+    pub ct_logs: &'static [&'static sct::Log],
 }
 
 impl ServerCertVerifier for WebPKIVerifier {
@@ -121,34 +194,75 @@ impl ServerCertVerifier for WebPKIVerifier {
     /// - Signed by a  trusted `RootCertStore` CA
     /// - Not Expired
     /// - Valid for DNS entry
-    /// - OCSP data is present
+    /// - Not revoked, according to any stapled OCSP response and
+    ///   `self.revocation_policy`
+    /// - If `self.ct_logs` is non-empty and the peer presented SCTs, at
+    ///   least one of them is valid for a log in `self.ct_logs`
     fn verify_server_cert(&self,
                           roots: &RootCertStore,
                           presented_certs: &[Certificate],
                           dns_name: webpki::DNSNameRef,
+                          scts: &SCTList,
                           ocsp_response: &[u8]) -> Result<ServerCertVerified, TLSError> {
         let (cert, chain, trustroots) = prepare(roots, presented_certs)?;
-        let now = (self.time)()?;
-        let cert = cert.verify_is_valid_tls_server_cert(SUPPORTED_SIG_ALGS,
+        let now_secs = (self.time)()?;
+        let now = webpki::Time::from_seconds_since_unix_epoch(now_secs);
+        let cert = cert.verify_is_valid_tls_server_cert(self.supported_sig_algs,
                 &webpki::TLSServerTrustAnchors(&trustroots), &chain, now)
             .map_err(TLSError::WebPKIError)
             .map(|_| cert)?;
 
-        if !ocsp_response.is_empty() {
-            debug!("Unvalidated OCSP response: {:?}", ocsp_response.to_vec());
+        if !self.ct_logs.is_empty() {
+            verify_scts(&presented_certs[0], scts, self.ct_logs)?;
+        }
+
+        if let Some(issuer) = presented_certs.get(1) {
+            ocsp::verify(&presented_certs[0], issuer, ocsp_response,
+                         self.revocation_policy, now_secs)?;
+        } else if self.revocation_policy == RevocationPolicy::HardFail {
+            return Err(TLSError::CertificateRevoked);
         }
 
         cert.verify_is_valid_for_dns_name(dns_name)
             .map_err(TLSError::WebPKIError)
             .map(|_| ServerCertVerified::assertion())
     }
+
+    fn supported_verify_schemes(&self) -> &'static [SignatureScheme] {
+        self.supported_schemes
+    }
 }
 
 impl WebPKIVerifier {
-    /// Create a new `WebPKIVerifier`
+    /// Create a new `WebPKIVerifier` accepting the default signature
+    /// algorithm profile (which includes Ed25519), and ignoring any
+    /// stapled OCSP response.
     pub fn new() -> WebPKIVerifier {
         WebPKIVerifier {
-            time: try_now,
+            time: now_unix_seconds,
+            supported_sig_algs: SUPPORTED_SIG_ALGS,
+            supported_schemes: SUPPORTED_SIG_SCHEMES,
+            revocation_policy: RevocationPolicy::Ignore,
+            ct_logs: &[],
+        }
+    }
+
+    /// Create a new `WebPKIVerifier` restricted to `sig_algs`, a caller-chosen
+    /// subset (or superset) of the webpki signature algorithms, and the
+    /// matching `schemes` that should be advertised for them.
+    ///
+    /// `sig_algs` and `schemes` must agree: every scheme offered must have a
+    /// corresponding algorithm the verifier is able to check, and vice versa.
+    /// This allows deployments to lock a connection down to a restricted
+    /// algorithm profile for compliance reasons.
+    pub fn with_signature_algorithms(sig_algs: SignatureAlgorithms,
+                                      schemes: &'static [SignatureScheme]) -> WebPKIVerifier {
+        WebPKIVerifier {
+            time: now_unix_seconds,
+            supported_sig_algs: sig_algs,
+            supported_schemes: schemes,
+            revocation_policy: RevocationPolicy::Ignore,
+            ct_logs: &[],
         }
     }
 }
@@ -185,6 +299,117 @@ fn try_now() -> Result<webpki::Time, TLSError> {
         .map_err( |_ | TLSError::FailedToGetCurrentTime)
 }
 
+fn now_unix_seconds() -> Result<u64, TLSError> {
+    unix_time_millis().map(|ms| ms / 1000)
+}
+
+/// What a `PinnedCertificateVerifier`'s pins are computed over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PinTarget {
+    /// Pin the whole end-entity certificate: its pin is the SHA-256 of its
+    /// DER encoding, so reissuing the certificate (even under the same key)
+    /// requires re-pinning.
+    Certificate,
+
+    /// Pin only the end-entity's `SubjectPublicKeyInfo`: its pin is the
+    /// SHA-256 of the raw public key bits, so a pin survives certificate
+    /// reissuance as long as the key doesn't change.
+    PublicKey,
+}
+
+/// A `ServerCertVerifier` that authenticates servers by a fixed set of
+/// pinned SHA-256 fingerprints, in the style of POSH (PKIX-over-Secure-HTTP,
+/// RFC 7711): the caller obtains the pins out of band (this crate does no
+/// HTTP, DNS, or other discovery) and any certificate matching one of them
+/// is trusted, independent of any CA.
+///
+/// This is useful for self-hosted services that want to pin a leaf
+/// certificate (or key) without standing up a full PKI.
+pub struct PinnedCertificateVerifier {
+    pins: Vec<[u8; 32]>,
+    target: PinTarget,
+}
+
+impl PinnedCertificateVerifier {
+    /// Construct a verifier that will only accept end-entity certificates
+    /// whose SHA-256 fingerprint (over the whole DER encoding) is a member
+    /// of `pins`.
+    ///
+    /// `pins` must not be empty; a verifier with no pins can never
+    /// succeed, by design, rather than silently falling back to some
+    /// other trust source.
+    pub fn new(pins: Vec<[u8; 32]>) -> Arc<dyn ServerCertVerifier> {
+        Arc::new(PinnedCertificateVerifier { pins, target: PinTarget::Certificate })
+    }
+
+    /// Like `new`, but in key-pinning mode: `pins` are SHA-256 fingerprints
+    /// of the raw public key bits in the end-entity's `SubjectPublicKeyInfo`
+    /// (the `subjectPublicKey` BIT STRING contents, not the whole DER
+    /// `SubjectPublicKeyInfo`) rather than of the whole certificate, so a
+    /// pin keeps working across certificate reissuance as long as the key
+    /// stays the same.
+    pub fn new_key_pins(pins: Vec<[u8; 32]>) -> Arc<dyn ServerCertVerifier> {
+        Arc::new(PinnedCertificateVerifier { pins, target: PinTarget::PublicKey })
+    }
+
+    fn is_pinned(&self, der: &[u8]) -> Result<bool, TLSError> {
+        let digest = match self.target {
+            PinTarget::Certificate => digest::digest(&digest::SHA256, der),
+            PinTarget::PublicKey => digest::digest(&digest::SHA256, &ocsp::spki_bits(der)?),
+        };
+        Ok(self.pins.iter().any(|pin| pin as &[u8] == digest.as_ref()))
+    }
+}
+
+impl ServerCertVerifier for PinnedCertificateVerifier {
+    /// Will verify the certificate is valid in the following ways:
+    /// - Its SHA-256 fingerprint (in key-pinning mode, that of its public
+    ///   key bits) is one of `self.pins`
+    /// - Not expired
+    /// - Valid for DNS entry
+    ///
+    /// `roots` is ignored: a pinned certificate is trusted on its own,
+    /// without chaining to any CA.  If the pin set is non-empty but
+    /// nothing matches, this fails with `TLSError::CertificatePinningFailed`
+    /// rather than falling back to web-PKI trust, so a stale pin cannot
+    /// silently downgrade the connection's security.
+    ///
+    /// `scts` and the OCSP response are ignored: a pinned leaf is already
+    /// as trusted as this verifier is able to make it, so Certificate
+    /// Transparency and revocation checking add nothing here.
+    fn verify_server_cert(&self,
+                          _roots: &RootCertStore,
+                          presented_certs: &[Certificate],
+                          dns_name: webpki::DNSNameRef,
+                          _scts: &SCTList,
+                          _ocsp_response: &[u8]) -> Result<ServerCertVerified, TLSError> {
+        if presented_certs.is_empty() {
+            return Err(TLSError::NoCertificatesPresented);
+        }
+
+        let ee_der = presented_certs[0].0.as_ref();
+        if !self.is_pinned(ee_der)? {
+            return Err(TLSError::CertificatePinningFailed);
+        }
+
+        // A pinned leaf is typically CA-issued (issuer != subject), so it
+        // cannot be chain-validated against itself as a trust anchor; we
+        // already trust it by fingerprint, so just check it's currently
+        // within its validity period and quotes the right name.
+        let (not_before, not_after) = ocsp::certificate_validity(ee_der)?;
+        let now = unix_time_millis()? / 1000;
+        if now < not_before || now > not_after {
+            return Err(TLSError::General("pinned certificate is not currently valid".into()));
+        }
+
+        webpki::EndEntityCert::from(ee_der)
+            .map_err(TLSError::WebPKIError)?
+            .verify_is_valid_for_dns_name(dns_name)
+            .map_err(TLSError::WebPKIError)
+            .map(|_| ServerCertVerified::assertion())
+    }
+}
+
 /// A `ClientCertVerifier` that will ensure that every client provides a trusted
 /// certificate, without any name checking.
 pub struct AllowAnyAuthenticatedClient {
@@ -278,19 +503,21 @@ impl ClientCertVerifier for NoClientAuth {
     }
 }
 
-fn convert_scheme(scheme: SignatureScheme) -> Result<(), TLSError> {
+fn convert_scheme(scheme: SignatureScheme, schemes: &[SignatureScheme]) -> Result<(), TLSError> {
     match scheme {
         // nb. for TLS1.2 the curve is not fixed by SignatureScheme.
         SignatureScheme::ECDSA_NISTP256_SHA256 |
         SignatureScheme::ECDSA_NISTP384_SHA384 |
 
+        SignatureScheme::ED25519 |
+
         SignatureScheme::RSA_PKCS1_SHA256 |
         SignatureScheme::RSA_PKCS1_SHA384 |
         SignatureScheme::RSA_PKCS1_SHA512 |
 
         SignatureScheme::RSA_PSS_SHA256 |
         SignatureScheme::RSA_PSS_SHA384 |
-        SignatureScheme::RSA_PSS_SHA512 => Ok(()),
+        SignatureScheme::RSA_PSS_SHA512 if schemes.contains(&scheme) => Ok(()),
 
         _ => {
             let error_msg = format!("received unadvertised sig scheme {:?}", scheme);
@@ -304,25 +531,38 @@ fn convert_scheme(scheme: SignatureScheme) -> Result<(), TLSError> {
 ///
 /// `cert` MUST have been authenticated before using this function,
 /// typically using `verify_cert`.
+///
+/// `schemes` is the set of schemes the caller is prepared to accept; pass
+/// `supported_verify_schemes()` for the default profile, or a verifier's
+/// own `supported_verify_schemes()` to honour a restricted configuration.
+///
+/// Breaking change: this function (and `verify_tls13` below) gained the
+/// `schemes` parameter here. Every caller elsewhere in the crate --
+/// `client/hs.rs` and `server/hs.rs` in particular -- must be updated in
+/// lockstep to pass the negotiated verifier's `supported_verify_schemes()`;
+/// those call sites aren't part of this checkout, so they couldn't be
+/// updated as part of this change.
 pub fn verify_signed_struct(message: &[u8],
                             cert: &Certificate,
-                            dss: &DigitallySignedStruct)
+                            dss: &DigitallySignedStruct,
+                            schemes: &[SignatureScheme])
                             -> Result<HandshakeSignatureValid, TLSError> {
-    convert_scheme(dss.scheme)?;
+    convert_scheme(dss.scheme, schemes)?;
     x509::verify_certificate_signature(&dss.sig.0, message, &cert.0, dss.scheme, false)
         .map_err(TLSError::WebPKIError)
         .map(|_| HandshakeSignatureValid::assertion())
 }
 
-fn convert_alg_tls13(scheme: SignatureScheme) -> Result<(), TLSError> {
+fn convert_alg_tls13(scheme: SignatureScheme, schemes: &[SignatureScheme]) -> Result<(), TLSError> {
     use crate::msgs::enums::SignatureScheme::*;
 
     match scheme {
         ECDSA_NISTP256_SHA256 |
         ECDSA_NISTP384_SHA384 |
+        ED25519 |
         RSA_PSS_SHA256 |
         RSA_PSS_SHA384 |
-        RSA_PSS_SHA512 => Ok(()),
+        RSA_PSS_SHA512 if schemes.contains(&scheme) => Ok(()),
         _ => {
             let error_msg = format!("received unsupported sig scheme {:?}", scheme);
             Err(TLSError::PeerMisbehavedError(error_msg))
@@ -330,12 +570,15 @@ fn convert_alg_tls13(scheme: SignatureScheme) -> Result<(), TLSError> {
     }
 }
 
+/// `schemes` is the set of schemes the caller is prepared to accept; see
+/// `verify_signed_struct` for details.
 pub fn verify_tls13(cert: &Certificate,
                     dss: &DigitallySignedStruct,
                     handshake_hash: &[u8],
-                    context_string_with_0: &[u8])
+                    context_string_with_0: &[u8],
+                    schemes: &[SignatureScheme])
                     -> Result<HandshakeSignatureValid, TLSError> {
-    convert_alg_tls13(dss.scheme)?;
+    convert_alg_tls13(dss.scheme, schemes)?;
 
     let mut msg = Vec::new();
     msg.resize(64, 0x20u8);
@@ -391,17 +634,458 @@ pub fn verify_scts(cert: &Certificate,
     Ok(())
 }
 
+/// The default signature scheme profile, now including Ed25519.  Custom
+/// verifiers should prefer their own `ServerCertVerifier::supported_verify_schemes`
+/// so the advertised set tracks what they actually accept.
 pub fn supported_verify_schemes() -> &'static [SignatureScheme] {
-    &[
-        SignatureScheme::ECDSA_NISTP384_SHA384,
-        SignatureScheme::ECDSA_NISTP256_SHA256,
-
-        SignatureScheme::RSA_PSS_SHA512,
-        SignatureScheme::RSA_PSS_SHA384,
-        SignatureScheme::RSA_PSS_SHA256,
-
-        SignatureScheme::RSA_PKCS1_SHA512,
-        SignatureScheme::RSA_PKCS1_SHA384,
-        SignatureScheme::RSA_PKCS1_SHA256,
-    ]
+    SUPPORTED_SIG_SCHEMES
+}
+
+/// A self-contained record of a successful server verification, capturing
+/// exactly the inputs that `verify_server_cert`/`verify_signed_struct`/
+/// `verify_tls13` consumed: the presented certificate chain, the
+/// handshake signature, and the precise bytes that were signed over.
+///
+/// Unlike the fire-and-forget `ServerCertVerified`/`HandshakeSignatureValid`
+/// markers, a `VerificationRecord` is a plain, inspectable value that can be
+/// handed to a third party, who can later call `replay` to reproduce the
+/// same assertions without the live connection -- e.g. for audit logging,
+/// or a TLSNotary-style proof that a given server identity was presented at
+/// a given time.
+///
+/// This type does not itself implement `serde::Serialize`/`Deserialize`:
+/// `dss` is a `msgs::handshake` type without serde support and `dns_name`
+/// is a `webpki` type this crate doesn't control, so serialization support
+/// would need to go through an intermediate representation rather than a
+/// derive here. Callers who need to move a record off-process today have
+/// to build that themselves from the public fields below.
+#[derive(Clone, Debug)]
+pub struct VerificationRecord {
+    /// The certificate chain as presented by the peer, end-entity first.
+    pub presented_certs: Vec<Certificate>,
+
+    /// The DNS name the end-entity certificate was checked against at
+    /// capture time (e.g. the SNI the client connected with). `replay`
+    /// re-checks the presented chain against this name, not just against
+    /// `roots`, so a record can't be replayed as a vouch for some other
+    /// name the peer was never asked to prove it owned.
+    pub dns_name: webpki::DNSName,
+
+    /// The handshake signature over `signed_message`.
+    pub dss: DigitallySignedStruct,
+
+    /// The exact bytes that were signed: for a TLS1.3 record this is the
+    /// 64 space-octet prefix, context string, and transcript hash as
+    /// constructed by `verify_tls13`; for a TLS1.2 record it is the raw
+    /// handshake `message` passed to `verify_signed_struct`.
+    pub signed_message: Vec<u8>,
+
+    /// `true` if `signed_message` was constructed using the TLS1.3
+    /// signing scheme (see `x509::verify_certificate_signature`).
+    pub is_tls13: bool,
+
+    /// The time verification was performed, as Unix seconds.
+    pub verified_at: u64,
+}
+
+impl VerificationRecord {
+    /// Capture a record of a TLS1.3 handshake signature, as verified by
+    /// `verify_tls13`.  `now` should be the same timestamp used for
+    /// certificate chain verification, e.g. from `(self.time)()`.
+    pub fn capture_tls13(presented_certs: &[Certificate],
+                         dns_name: webpki::DNSNameRef,
+                         dss: &DigitallySignedStruct,
+                         handshake_hash: &[u8],
+                         context_string_with_0: &[u8],
+                         now: u64) -> VerificationRecord {
+        let mut signed_message = Vec::new();
+        signed_message.resize(64, 0x20u8);
+        signed_message.extend_from_slice(context_string_with_0);
+        signed_message.extend_from_slice(handshake_hash);
+
+        VerificationRecord {
+            presented_certs: presented_certs.to_vec(),
+            dns_name: dns_name.to_owned(),
+            dss: dss.clone(),
+            signed_message,
+            is_tls13: true,
+            verified_at: now,
+        }
+    }
+
+    /// Capture a record of a TLS1.2 handshake signature, as verified by
+    /// `verify_signed_struct`.  `now` should be the same timestamp used
+    /// for certificate chain verification.
+    pub fn capture_tls12(presented_certs: &[Certificate],
+                         dns_name: webpki::DNSNameRef,
+                         dss: &DigitallySignedStruct,
+                         message: &[u8],
+                         now: u64) -> VerificationRecord {
+        VerificationRecord {
+            presented_certs: presented_certs.to_vec(),
+            dns_name: dns_name.to_owned(),
+            dss: dss.clone(),
+            signed_message: message.to_vec(),
+            is_tls13: false,
+            verified_at: now,
+        }
+    }
+
+    /// Replay this record against `roots`, reproducing the
+    /// `ServerCertVerified` and `HandshakeSignatureValid` assertions
+    /// without a live connection.
+    ///
+    /// This re-runs the chain, name and signature checks that
+    /// `verify_server_cert` and `verify_signed_struct`/`verify_tls13` did at
+    /// capture time: that the presented chain was valid for `roots` at
+    /// `self.verified_at`, that it was valid for `self.dns_name`, that
+    /// `self.dss.scheme` is in `schemes`, and that `self.dss` is a valid
+    /// signature over `self.signed_message` from the end-entity
+    /// certificate's key.  The SCT and OCSP checks of `verify_server_cert`
+    /// are not repeated.
+    ///
+    /// `sig_algs` must be the same signature-algorithm profile the
+    /// `WebPKIVerifier` that produced this record was configured with
+    /// (its `supported_sig_algs`, e.g. `SUPPORTED_SIG_ALGS` for a verifier
+    /// built with `WebPKIVerifier::new`). Passing a wider profile than was
+    /// actually enforced live would let a replay succeed under algorithms
+    /// the original connection could never have used.
+    ///
+    /// `schemes` must likewise be the profile's `supported_verify_schemes()`
+    /// (e.g. `SUPPORTED_SIG_SCHEMES`, or whatever was passed to
+    /// `with_signature_algorithms`): a `VerificationRecord` can be built
+    /// directly by `capture_tls12`/`capture_tls13`, or reconstructed from
+    /// serialized data, without ever having passed through a live
+    /// `verify_signed_struct`/`verify_tls13` call, so `replay` must apply
+    /// the same scheme whitelist those functions do before trusting
+    /// `self.dss.scheme`.
+    pub fn replay(&self, roots: &RootCertStore, sig_algs: SignatureAlgorithms,
+                 schemes: &[SignatureScheme])
+                 -> Result<(ServerCertVerified, HandshakeSignatureValid), TLSError> {
+        let (cert, chain, trustroots) = prepare(roots, &self.presented_certs)?;
+        let when = webpki::Time::from_seconds_since_unix_epoch(self.verified_at);
+
+        cert.verify_is_valid_tls_server_cert(sig_algs,
+                &webpki::TLSServerTrustAnchors(&trustroots), &chain, when)
+            .map_err(TLSError::WebPKIError)?;
+
+        cert.verify_is_valid_for_dns_name(self.dns_name.as_ref())
+            .map_err(TLSError::WebPKIError)?;
+
+        if self.is_tls13 {
+            convert_alg_tls13(self.dss.scheme, schemes)?;
+        } else {
+            convert_scheme(self.dss.scheme, schemes)?;
+        }
+
+        x509::verify_certificate_signature(&self.dss.sig.0,
+                                           &self.signed_message,
+                                           &self.presented_certs[0].0,
+                                           self.dss.scheme,
+                                           self.is_tls13)
+            .map_err(TLSError::WebPKIError)?;
+
+        Ok((ServerCertVerified::assertion(), HandshakeSignatureValid::assertion()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_der::*;
+
+    /// The verification marker types (`ServerCertVerified` etc.) are
+    /// deliberately zero-sized and don't derive `Debug`, so we can't use
+    /// `unwrap_err`/`expect_err` directly on results that carry them.
+    fn assert_verification_fails<T>(result: Result<T, TLSError>, msg: &'static str) {
+        assert!(result.is_err(), "{}", msg);
+    }
+
+    const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+    const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+    const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+
+    /// Build a minimal, self-signed v3 certificate for `key`, valid over
+    /// `[not_before, not_after]` (`GeneralizedTime` strings, e.g.
+    /// `"20200101000000Z"`), with a single `dNSName` SAN of `dns_name`.
+    ///
+    /// `issuer == subject` and the outer signature is genuinely produced by
+    /// `key`, so this validates as a one-certificate chain directly against
+    /// a `RootCertStore` containing the same DER.
+    fn build_self_signed_cert(key: &TestKey, dns_name: &str,
+                              not_before: &str, not_after: &str) -> Vec<u8> {
+        let spki = seq(&[
+            &seq(&[&oid(OID_EC_PUBLIC_KEY), &oid(OID_PRIME256V1)]),
+            &bit_string(&key.public_key),
+        ]);
+        let extensions = explicit(3, &seq(&[&dns_name_san(dns_name)]));
+        let tbs = seq(&[
+            &explicit(0, &integer(&[0x02])), // version: v3
+            &integer(&[0x01]),
+            &seq(&[&oid(OID_ECDSA_WITH_SHA256)]),
+            &empty_name(),
+            &seq(&[&generalized_time(not_before), &generalized_time(not_after)]),
+            &empty_name(),
+            &spki,
+            &extensions,
+        ]);
+        let signature = sign(key, &tbs);
+        seq(&[
+            &tbs,
+            &seq(&[&oid(OID_ECDSA_WITH_SHA256)]),
+            &bit_string(&signature),
+        ])
+    }
+
+    fn sha256_fingerprint(der: &[u8]) -> [u8; 32] {
+        let digest = digest::digest(&digest::SHA256, der);
+        let mut out = [0u8; 32];
+        out.copy_from_slice(digest.as_ref());
+        out
+    }
+
+    fn trusted_roots(cert_der: &[u8]) -> RootCertStore {
+        let mut roots = RootCertStore::empty();
+        roots.add(&Certificate(cert_der.to_vec())).unwrap();
+        roots
+    }
+
+    const VALID_FROM: &str = "20200101000000Z";
+    const VALID_UNTIL: &str = "20300101000000Z";
+    const EXPIRED_FROM: &str = "20000101000000Z";
+    const EXPIRED_UNTIL: &str = "20100101000000Z";
+
+    // -- PinnedCertificateVerifier (chunk0-2) --
+
+    #[test]
+    fn pinned_certificate_with_matching_fingerprint_is_accepted() {
+        let key = generate_key();
+        let cert_der = build_self_signed_cert(&key, "example.com", VALID_FROM, VALID_UNTIL);
+        let verifier = PinnedCertificateVerifier::new(vec![sha256_fingerprint(&cert_der)]);
+        let dns_name = webpki::DNSNameRef::try_from_ascii_str("example.com").unwrap();
+
+        verifier.verify_server_cert(&RootCertStore::empty(), &[Certificate(cert_der)],
+                                    dns_name, &SCTList::new(), &[])
+            .expect("a certificate matching the pin set should verify");
+    }
+
+    #[test]
+    fn certificate_not_in_the_pin_set_is_rejected_even_though_it_is_otherwise_valid() {
+        let key = generate_key();
+        let cert_der = build_self_signed_cert(&key, "example.com", VALID_FROM, VALID_UNTIL);
+        // Pin set deliberately doesn't contain this certificate's fingerprint.
+        let verifier = PinnedCertificateVerifier::new(vec![[0u8; 32]]);
+        let dns_name = webpki::DNSNameRef::try_from_ascii_str("example.com").unwrap();
+
+        match verifier.verify_server_cert(&RootCertStore::empty(), &[Certificate(cert_der)],
+                                          dns_name, &SCTList::new(), &[]) {
+            Err(TLSError::CertificatePinningFailed) => {}
+            Err(other) => panic!("expected CertificatePinningFailed, got {:?}", other),
+            Ok(_) => panic!("expected CertificatePinningFailed, but verification succeeded"),
+        }
+    }
+
+    #[test]
+    fn expired_certificate_is_rejected_even_if_pinned() {
+        let key = generate_key();
+        let cert_der = build_self_signed_cert(&key, "example.com", EXPIRED_FROM, EXPIRED_UNTIL);
+        let verifier = PinnedCertificateVerifier::new(vec![sha256_fingerprint(&cert_der)]);
+        let dns_name = webpki::DNSNameRef::try_from_ascii_str("example.com").unwrap();
+
+        assert_verification_fails(
+            verifier.verify_server_cert(&RootCertStore::empty(), &[Certificate(cert_der)],
+                                        dns_name, &SCTList::new(), &[]),
+            "an expired certificate must not verify just because it's pinned");
+    }
+
+    #[test]
+    fn pinned_certificate_for_the_wrong_dns_name_is_rejected() {
+        let key = generate_key();
+        let cert_der = build_self_signed_cert(&key, "example.com", VALID_FROM, VALID_UNTIL);
+        let verifier = PinnedCertificateVerifier::new(vec![sha256_fingerprint(&cert_der)]);
+        let wrong_name = webpki::DNSNameRef::try_from_ascii_str("not-example.com").unwrap();
+
+        assert_verification_fails(
+            verifier.verify_server_cert(&RootCertStore::empty(), &[Certificate(cert_der)],
+                                        wrong_name, &SCTList::new(), &[]),
+            "a pin match must not paper over a DNS name mismatch");
+    }
+
+    #[test]
+    fn key_pinned_certificate_with_matching_spki_is_accepted() {
+        let key = generate_key();
+        let cert_der = build_self_signed_cert(&key, "example.com", VALID_FROM, VALID_UNTIL);
+        let spki_hash = digest::digest(&digest::SHA256, &ocsp::spki_bits(&cert_der).unwrap());
+        let mut pin = [0u8; 32];
+        pin.copy_from_slice(spki_hash.as_ref());
+        let verifier = PinnedCertificateVerifier::new_key_pins(vec![pin]);
+        let dns_name = webpki::DNSNameRef::try_from_ascii_str("example.com").unwrap();
+
+        verifier.verify_server_cert(&RootCertStore::empty(), &[Certificate(cert_der)],
+                                    dns_name, &SCTList::new(), &[])
+            .expect("a certificate whose SPKI matches the pin set should verify");
+    }
+
+    #[test]
+    fn key_pinned_certificate_is_rejected_under_whole_certificate_pinning() {
+        let key = generate_key();
+        let cert_der = build_self_signed_cert(&key, "example.com", VALID_FROM, VALID_UNTIL);
+        let spki_hash = digest::digest(&digest::SHA256, &ocsp::spki_bits(&cert_der).unwrap());
+        let mut pin = [0u8; 32];
+        pin.copy_from_slice(spki_hash.as_ref());
+        // The pin matches the SPKI, not the whole-certificate fingerprint,
+        // so a `new` (not `new_key_pins`) verifier must reject it.
+        let verifier = PinnedCertificateVerifier::new(vec![pin]);
+        let dns_name = webpki::DNSNameRef::try_from_ascii_str("example.com").unwrap();
+
+        match verifier.verify_server_cert(&RootCertStore::empty(), &[Certificate(cert_der)],
+                                          dns_name, &SCTList::new(), &[]) {
+            Err(TLSError::CertificatePinningFailed) => {}
+            Err(other) => panic!("expected CertificatePinningFailed, got {:?}", other),
+            Ok(_) => panic!("a key pin must not be accepted as a whole-certificate pin"),
+        }
+    }
+
+    // -- signature-scheme profile gating (chunk0-1) --
+
+    #[test]
+    fn convert_scheme_rejects_a_scheme_outside_the_configured_profile() {
+        let restricted = &[SignatureScheme::RSA_PKCS1_SHA256];
+        let err = convert_scheme(SignatureScheme::ED25519, restricted).unwrap_err();
+        match err {
+            TLSError::PeerMisbehavedError(_) => {}
+            other => panic!("expected PeerMisbehavedError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn convert_scheme_and_convert_alg_tls13_accept_ed25519_in_the_default_profile() {
+        convert_scheme(SignatureScheme::ED25519, SUPPORTED_SIG_SCHEMES).unwrap();
+        convert_alg_tls13(SignatureScheme::ED25519, SUPPORTED_SIG_SCHEMES).unwrap();
+    }
+
+    #[test]
+    fn with_signature_algorithms_advertises_exactly_the_restricted_scheme_list() {
+        let restricted: &[SignatureScheme] = &[SignatureScheme::ECDSA_NISTP256_SHA256];
+        let verifier = WebPKIVerifier::with_signature_algorithms(SUPPORTED_SIG_ALGS, restricted);
+
+        assert_eq!(verifier.supported_verify_schemes(), restricted);
+        convert_scheme(SignatureScheme::ED25519, verifier.supported_verify_schemes())
+            .expect_err("a scheme outside the restricted profile must still be rejected");
+    }
+
+    // -- CT log enforcement (chunk0-4) --
+
+    const TEST_CT_LOG: sct::Log = sct::Log {
+        description: "test log",
+        log_id: &[0u8; 32],
+        key: &[0u8; 91],
+        operated_by: "test operator",
+    };
+
+    #[test]
+    fn non_empty_ct_logs_with_no_presented_scts_currently_passes() {
+        // Documents the existing behaviour: `verify_scts` only hard-fails
+        // when SCTs were actually presented but none of them validated
+        // against `logs`. A configured, non-empty `ct_logs` with an empty
+        // `scts` list is treated like "the peer sent no CT information",
+        // not "the peer failed CT" -- so this is not (by itself) a way to
+        // mandate CT for every connection.
+        let key = generate_key();
+        let cert_der = build_self_signed_cert(&key, "example.com", VALID_FROM, VALID_UNTIL);
+        let cert = Certificate(cert_der);
+        let logs: &[&sct::Log] = &[&TEST_CT_LOG];
+
+        verify_scts(&cert, &SCTList::new(), logs)
+            .expect("an empty SCT list is not currently treated as a CT failure");
+    }
+
+    // -- VerificationRecord::replay (chunk0-5) --
+
+    const REPLAYED_AT: u64 = 1_650_000_000; // within [VALID_FROM, VALID_UNTIL]
+
+    fn signed_tls12_record(key: &TestKey, cert_der: &[u8], message: &[u8]) -> VerificationRecord {
+        let sig = sign(key, message);
+        let dss = DigitallySignedStruct::new(SignatureScheme::ECDSA_NISTP256_SHA256, sig);
+        let presented = vec![Certificate(cert_der.to_vec())];
+        let dns_name = webpki::DNSNameRef::try_from_ascii_str("example.com").unwrap();
+        VerificationRecord::capture_tls12(&presented, dns_name, &dss, message, REPLAYED_AT)
+    }
+
+    #[test]
+    fn replay_reproduces_a_successful_tls12_verification() {
+        let key = generate_key();
+        let cert_der = build_self_signed_cert(&key, "example.com", VALID_FROM, VALID_UNTIL);
+        let message = b"some handshake transcript bytes";
+        let record = signed_tls12_record(&key, &cert_der, message);
+
+        record.replay(&trusted_roots(&cert_der), SUPPORTED_SIG_ALGS, SUPPORTED_SIG_SCHEMES)
+            .expect("a record captured from a valid verification should replay successfully");
+    }
+
+    #[test]
+    fn replay_rejects_a_record_when_checked_against_the_wrong_roots() {
+        let key = generate_key();
+        let cert_der = build_self_signed_cert(&key, "example.com", VALID_FROM, VALID_UNTIL);
+        let message = b"some handshake transcript bytes";
+        let record = signed_tls12_record(&key, &cert_der, message);
+
+        let other_key = generate_key();
+        let other_cert_der = build_self_signed_cert(&other_key, "example.com", VALID_FROM, VALID_UNTIL);
+
+        assert_verification_fails(
+            record.replay(&trusted_roots(&other_cert_der), SUPPORTED_SIG_ALGS, SUPPORTED_SIG_SCHEMES),
+            "replaying against roots that never vouched for this cert must fail");
+    }
+
+    #[test]
+    fn replay_rejects_a_record_whose_captured_dns_name_the_cert_never_covered() {
+        let key = generate_key();
+        // This cert's only SAN is "example.com", but nothing stops a
+        // `VerificationRecord` from being hand-built (or deserialized)
+        // with `dns_name` set to some other name the cert was never
+        // checked against live.
+        let cert_der = build_self_signed_cert(&key, "example.com", VALID_FROM, VALID_UNTIL);
+        let message = b"some handshake transcript bytes";
+        let sig = sign(&key, message);
+        let dss = DigitallySignedStruct::new(SignatureScheme::ECDSA_NISTP256_SHA256, sig);
+        let presented = vec![Certificate(cert_der.clone())];
+        let wrong_name = webpki::DNSNameRef::try_from_ascii_str("not-example.com").unwrap();
+        let record = VerificationRecord::capture_tls12(&presented, wrong_name, &dss, message, REPLAYED_AT);
+
+        assert_verification_fails(
+            record.replay(&trusted_roots(&cert_der), SUPPORTED_SIG_ALGS, SUPPORTED_SIG_SCHEMES),
+            "replay must check the chain against the captured dns_name, not just the roots");
+    }
+
+    #[test]
+    fn replay_rejects_a_record_whose_signed_message_was_tampered_with() {
+        let key = generate_key();
+        let cert_der = build_self_signed_cert(&key, "example.com", VALID_FROM, VALID_UNTIL);
+        let message = b"some handshake transcript bytes";
+        let mut record = signed_tls12_record(&key, &cert_der, message);
+        record.signed_message[0] ^= 0xff;
+
+        assert_verification_fails(
+            record.replay(&trusted_roots(&cert_der), SUPPORTED_SIG_ALGS, SUPPORTED_SIG_SCHEMES),
+            "a tampered signed message must not re-verify");
+    }
+
+    #[test]
+    fn replay_rejects_a_scheme_outside_the_caller_supplied_profile() {
+        let key = generate_key();
+        let cert_der = build_self_signed_cert(&key, "example.com", VALID_FROM, VALID_UNTIL);
+        let message = b"some handshake transcript bytes";
+        let record = signed_tls12_record(&key, &cert_der, message);
+
+        // Cryptographically and chain-wise this record is fine; restricting
+        // the replay profile to exclude ECDSA must still reject it, exactly
+        // as a live `verify_signed_struct` would have for the same peer.
+        let restricted: &[SignatureScheme] = &[SignatureScheme::RSA_PKCS1_SHA256];
+        match record.replay(&trusted_roots(&cert_der), SUPPORTED_SIG_ALGS, restricted) {
+            Err(TLSError::PeerMisbehavedError(_)) => {}
+            Err(other) => panic!("expected PeerMisbehavedError, got {:?}", other),
+            Ok(_) => panic!("expected PeerMisbehavedError, but replay succeeded"),
+        }
+    }
 }
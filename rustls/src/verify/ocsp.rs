@@ -0,0 +1,847 @@
+use ring::digest;
+use webpki;
+
+use crate::error::TLSError;
+use crate::key::Certificate;
+
+/// Policy controlling how a stapled OCSP response affects certificate
+/// verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevocationPolicy {
+    /// Don't look at stapled OCSP responses at all.  This is the
+    /// historic behaviour of `WebPKIVerifier`.
+    Ignore,
+
+    /// Accept the certificate if no response was stapled, or if the
+    /// response couldn't be validated or has expired; only reject the
+    /// handshake on an explicit `revoked` status.
+    SoftFail,
+
+    /// Require a well-formed, fresh, `good` OCSP response; reject
+    /// everything else, including a missing response.
+    HardFail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CertStatus {
+    Good,
+    Revoked,
+    Unknown,
+}
+
+/// Validate a DER-encoded stapled OCSP `BasicOCSPResponse` covering `ee`,
+/// whose issuer is `issuer` (the next certificate in the chain), applying
+/// `policy`.
+///
+/// `now` is Unix time in seconds; `WebPKIVerifier` passes the value of its
+/// `time` provider, the same "now" it uses for certificate chain validity.
+pub fn verify(ee: &Certificate,
+              issuer: &Certificate,
+              response: &[u8],
+              policy: RevocationPolicy,
+              now: u64) -> Result<(), TLSError> {
+    if policy == RevocationPolicy::Ignore {
+        return Ok(());
+    }
+
+    if response.is_empty() {
+        return match policy {
+            RevocationPolicy::HardFail => Err(TLSError::CertificateRevoked),
+            _ => Ok(()),
+        };
+    }
+
+    match check_response(ee, issuer, response, now) {
+        Ok(CertStatus::Good) => Ok(()),
+        Ok(CertStatus::Revoked) => Err(TLSError::CertificateRevoked),
+        Ok(CertStatus::Unknown) => {
+            if policy == RevocationPolicy::HardFail {
+                Err(TLSError::CertificateRevoked)
+            } else {
+                Ok(())
+            }
+        }
+        Err(e) => {
+            if policy == RevocationPolicy::HardFail {
+                Err(e)
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+fn check_response(ee: &Certificate,
+                   issuer: &Certificate,
+                   response: &[u8],
+                   now: u64) -> Result<CertStatus, TLSError> {
+    let basic = parse_basic_response(response)?;
+    verify_signature(issuer, &basic)?;
+
+    let issuer_name_hash = digest::digest(basic.cert_id.hash_alg, &subject_der(&issuer.0)?);
+    let issuer_key_hash = digest::digest(basic.cert_id.hash_alg, &spki_bits(&issuer.0)?);
+    let ee_serial = serial_der(&ee.0)?;
+
+    if issuer_name_hash.as_ref() != basic.cert_id.issuer_name_hash
+        || issuer_key_hash.as_ref() != basic.cert_id.issuer_key_hash
+        || ee_serial != basic.cert_id.serial_number {
+        return Err(TLSError::General("OCSP response is for a different certificate".into()));
+    }
+
+    if now < basic.this_update {
+        return Err(TLSError::General("OCSP response is not yet valid".into()));
+    }
+
+    if let Some(next_update) = basic.next_update {
+        if now > next_update {
+            return Err(TLSError::General("OCSP response has expired".into()));
+        }
+    }
+
+    Ok(basic.status)
+}
+
+fn verify_signature(issuer: &Certificate, basic: &BasicResponse) -> Result<(), TLSError> {
+    let signer = webpki::EndEntityCert::from(&issuer.0)
+        .map_err(TLSError::WebPKIError)?;
+    signer.verify_signature(basic.sig_alg, &basic.tbs_response_data, &basic.signature)
+        .map_err(TLSError::WebPKIError)
+}
+
+struct CertID<'a> {
+    hash_alg: &'static digest::Algorithm,
+    issuer_name_hash: &'a [u8],
+    issuer_key_hash: &'a [u8],
+    serial_number: &'a [u8],
+}
+
+struct BasicResponse<'a> {
+    tbs_response_data: &'a [u8],
+    sig_alg: &'static webpki::SignatureAlgorithm,
+    signature: &'a [u8],
+    cert_id: CertID<'a>,
+    status: CertStatus,
+    this_update: u64,
+    next_update: Option<u64>,
+}
+
+/// `OCSPResponse ::= SEQUENCE { responseStatus ENUMERATED,
+///                              responseBytes [0] EXPLICIT ResponseBytes OPTIONAL }`
+/// `ResponseBytes ::= SEQUENCE { responseType OBJECT IDENTIFIER,
+///                               response OCTET STRING }`
+/// and the `response` octet string contains a DER `BasicOCSPResponse`.
+fn parse_basic_response(der: &[u8]) -> Result<BasicResponse, TLSError> {
+    let mut outer = der::Reader::new(der)?;
+    let mut top = outer.read_sequence()?;
+    let status = top.read_enum()?;
+    if status != 0 {
+        return Err(TLSError::General("OCSP responder did not return a successful status".into()));
+    }
+
+    let mut response_bytes = top.read_explicit(0)?.read_sequence()?;
+    let response_type = response_bytes.read_raw(0x06)?;
+    if response_type != OID_PKIX_OCSP_BASIC {
+        return Err(TLSError::General("unsupported OCSP responseType".into()));
+    }
+    let response = response_bytes.read_octet_string()?;
+
+    let mut basic = der::Reader::new(response)?.read_sequence()?;
+    let tbs_response_data = basic.read_raw_element(der::SEQUENCE)?;
+    let sig_alg = basic.read_sequence()?.read_signature_algorithm()?;
+    let signature = basic.read_bit_string()?;
+
+    let mut tbs = der::Reader::new(tbs_response_data)?.read_sequence()?;
+    if tbs.peek_tag() == Some(0xa0) {
+        tbs.skip_any()?; // version, default v1
+    }
+    tbs.skip_any()?; // responderID (either [1] byName or [2] byKey)
+    tbs.skip_any()?; // producedAt
+
+    let mut responses = tbs.read_sequence()?;
+    let mut single = responses.read_sequence()?;
+
+    let mut cert_id_reader = single.read_sequence()?;
+    let hash_alg = cert_id_reader.read_sequence()?.read_hash_algorithm()?;
+    let issuer_name_hash = cert_id_reader.read_octet_string()?;
+    let issuer_key_hash = cert_id_reader.read_octet_string()?;
+    let serial_number = cert_id_reader.read_integer()?;
+
+    // `CertStatus ::= CHOICE { good [0] IMPLICIT NULL, revoked [1] IMPLICIT
+    // RevokedInfo, unknown [2] IMPLICIT UnknownInfo }`.  `good`/`unknown`
+    // are IMPLICIT NULL/UnknownInfo (primitive, `0x80`/`0x82`); only
+    // `revoked` is a SEQUENCE (constructed, `0xa1`).
+    let (status_tag, status_value) = single.read_any()?;
+    let status = match status_tag {
+        0x80 => CertStatus::Good,
+        0xa1 => CertStatus::Revoked,
+        0x82 => CertStatus::Unknown,
+        _ => return Err(TLSError::General("invalid OCSP certStatus".into())),
+    };
+    let _ = status_value;
+
+    let this_update = der::generalized_time_to_unix_seconds(single.read_raw(der::GENERALIZED_TIME)?)?;
+    let next_update = if single.peek_tag() == Some(0xa0) {
+        Some(der::generalized_time_to_unix_seconds(single.read_explicit(0)?.read_raw(der::GENERALIZED_TIME)?)?)
+    } else {
+        None
+    };
+
+    Ok(BasicResponse {
+        tbs_response_data,
+        sig_alg,
+        signature,
+        cert_id: CertID { hash_alg, issuer_name_hash, issuer_key_hash, serial_number },
+        status,
+        this_update,
+        next_update,
+    })
+}
+
+/// Pull `TBSCertificate.subject` out of a DER `Certificate`.
+fn subject_der(cert_der: &[u8]) -> Result<Vec<u8>, TLSError> {
+    let mut tbs = tbs_certificate(cert_der)?;
+    skip_version_and_serial(&mut tbs)?;
+    tbs.skip_any()?; // signature AlgorithmIdentifier
+    tbs.skip_any()?; // issuer Name
+    tbs.skip_any()?; // validity
+    Ok(tbs.read_raw_element(der::SEQUENCE)?.to_vec())
+}
+
+/// Pull `TBSCertificate.subjectPublicKeyInfo.subjectPublicKey` (the raw
+/// key bits, without the BIT STRING's unused-bits byte) out of a DER
+/// `Certificate`.  Used by `PinnedCertificateVerifier`'s key-pinning mode,
+/// in addition to cross-checking a stapled OCSP response's `issuer_key_hash`.
+pub fn spki_bits(cert_der: &[u8]) -> Result<Vec<u8>, TLSError> {
+    let mut tbs = tbs_certificate(cert_der)?;
+    skip_version_and_serial(&mut tbs)?;
+    tbs.skip_any()?; // signature AlgorithmIdentifier
+    tbs.skip_any()?; // issuer Name
+    tbs.skip_any()?; // validity
+    tbs.skip_any()?; // subject Name
+    let mut spki = tbs.read_sequence()?;
+    spki.skip_any()?; // algorithm
+    Ok(spki.read_bit_string()?.to_vec())
+}
+
+fn serial_der(cert_der: &[u8]) -> Result<Vec<u8>, TLSError> {
+    let mut tbs = tbs_certificate(cert_der)?;
+    if tbs.peek_tag() == Some(0xa0) {
+        tbs.skip_any()?;
+    }
+    Ok(tbs.read_integer()?.to_vec())
+}
+
+/// Pull `TBSCertificate.validity` out of a DER `Certificate`, as
+/// `(not_before, not_after)` Unix seconds.  Used by
+/// `PinnedCertificateVerifier`, which authenticates a leaf by its pinned
+/// fingerprint rather than by chain-building, so it cannot rely on
+/// webpki's own chain-validation expiry check.
+pub fn certificate_validity(cert_der: &[u8]) -> Result<(u64, u64), TLSError> {
+    let mut tbs = tbs_certificate(cert_der)?;
+    skip_version_and_serial(&mut tbs)?;
+    tbs.skip_any()?; // signature AlgorithmIdentifier
+    tbs.skip_any()?; // issuer Name
+
+    let mut validity = tbs.read_sequence()?;
+    let not_before = validity.read_time()?;
+    let not_after = validity.read_time()?;
+    Ok((not_before, not_after))
+}
+
+fn tbs_certificate(cert_der: &[u8]) -> Result<der::Reader, TLSError> {
+    der::Reader::new(cert_der)?.read_sequence()?.read_sequence()
+}
+
+fn skip_version_and_serial(tbs: &mut der::Reader) -> Result<(), TLSError> {
+    if tbs.peek_tag() == Some(0xa0) {
+        tbs.skip_any()?;
+    }
+    tbs.skip_any()?; // serialNumber
+    Ok(())
+}
+
+/// A minimal DER reader covering only the constructs needed to parse an
+/// OCSP `BasicOCSPResponse` and the handful of `Certificate` fields we
+/// cross-check it against.  This is not a general-purpose ASN.1 library:
+/// it supports definite-length encodings with up to a 2-byte length form,
+/// which is sufficient for the certificates and responses this crate
+/// otherwise already accepts via webpki.
+mod der {
+    use ring::digest;
+    use webpki;
+
+    use crate::error::TLSError;
+
+    pub const SEQUENCE: u8 = 0x30;
+    pub const UTC_TIME: u8 = 0x17;
+    pub const GENERALIZED_TIME: u8 = 0x18;
+
+    pub struct Reader<'a> {
+        data: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        pub fn new(data: &'a [u8]) -> Result<Reader<'a>, TLSError> {
+            Ok(Reader { data, pos: 0 })
+        }
+
+        fn err() -> TLSError {
+            TLSError::General("invalid OCSP/certificate DER encoding".into())
+        }
+
+        pub fn peek_tag(&self) -> Option<u8> {
+            self.data.get(self.pos).cloned()
+        }
+
+        fn read_tlv(&mut self) -> Result<(u8, &'a [u8]), TLSError> {
+            let tag = *self.data.get(self.pos).ok_or_else(Self::err)?;
+            let mut idx = self.pos + 1;
+            let first_len = *self.data.get(idx).ok_or_else(Self::err)?;
+            idx += 1;
+
+            let len = if first_len & 0x80 == 0 {
+                first_len as usize
+            } else {
+                let n_bytes = (first_len & 0x7f) as usize;
+                if n_bytes == 0 || n_bytes > 2 {
+                    return Err(Self::err());
+                }
+                let mut len = 0usize;
+                for _ in 0..n_bytes {
+                    len = (len << 8) | (*self.data.get(idx).ok_or_else(Self::err)? as usize);
+                    idx += 1;
+                }
+                len
+            };
+
+            let value = self.data.get(idx..idx + len).ok_or_else(Self::err)?;
+            self.pos = idx + len;
+            Ok((tag, value))
+        }
+
+        pub fn read_any(&mut self) -> Result<(u8, &'a [u8]), TLSError> {
+            self.read_tlv()
+        }
+
+        pub fn skip_any(&mut self) -> Result<(), TLSError> {
+            self.read_tlv().map(|_| ())
+        }
+
+        pub fn read_raw(&mut self, expected_tag: u8) -> Result<&'a [u8], TLSError> {
+            let (tag, value) = self.read_tlv()?;
+            if tag != expected_tag {
+                return Err(Self::err());
+            }
+            Ok(value)
+        }
+
+        /// Like `read_raw`, but returns the whole TLV (tag + length +
+        /// value), for re-parsing the value as a nested structure while
+        /// also keeping the exact signed bytes around.
+        pub fn read_raw_element(&mut self, expected_tag: u8) -> Result<&'a [u8], TLSError> {
+            let start = self.pos;
+            let (tag, _) = self.read_tlv()?;
+            if tag != expected_tag {
+                return Err(Self::err());
+            }
+            Ok(&self.data[start..self.pos])
+        }
+
+        pub fn read_sequence(&mut self) -> Result<Reader<'a>, TLSError> {
+            let value = self.read_raw(SEQUENCE)?;
+            Reader::new(value)
+        }
+
+        /// Reads a `[n] EXPLICIT` context tag and returns a reader over
+        /// its (single) inner value.
+        pub fn read_explicit(&mut self, n: u8) -> Result<Reader<'a>, TLSError> {
+            let value = self.read_raw(0xa0 | n)?;
+            Reader::new(value)
+        }
+
+        pub fn read_octet_string(&mut self) -> Result<&'a [u8], TLSError> {
+            self.read_raw(0x04)
+        }
+
+        pub fn read_integer(&mut self) -> Result<&'a [u8], TLSError> {
+            self.read_raw(0x02)
+        }
+
+        pub fn read_enum(&mut self) -> Result<i64, TLSError> {
+            let value = self.read_raw(0x0a)?;
+            Ok(value.iter().fold(0i64, |acc, b| (acc << 8) | (*b as i64)))
+        }
+
+        pub fn read_bit_string(&mut self) -> Result<&'a [u8], TLSError> {
+            let value = self.read_raw(0x03)?;
+            // first byte is the count of unused bits in the last octet;
+            // we only deal with byte-aligned keys/signatures.
+            value.get(1..).ok_or_else(Self::err)
+        }
+
+        /// `AlgorithmIdentifier ::= SEQUENCE { algorithm OBJECT IDENTIFIER, parameters ANY OPTIONAL }`
+        /// mapped to the webpki signature algorithm it names.
+        pub fn read_signature_algorithm(&mut self) -> Result<&'static webpki::SignatureAlgorithm, TLSError> {
+            let oid = self.read_raw(0x06)?;
+            self.skip_remaining();
+            super::oid_to_signature_algorithm(oid)
+        }
+
+        /// `AlgorithmIdentifier` naming a hash algorithm, as used inside `CertID`.
+        pub fn read_hash_algorithm(&mut self) -> Result<&'static digest::Algorithm, TLSError> {
+            let oid = self.read_raw(0x06)?;
+            self.skip_remaining();
+            super::oid_to_digest_algorithm(oid)
+        }
+
+        fn skip_remaining(&mut self) {
+            self.pos = self.data.len();
+        }
+
+        /// `Time ::= CHOICE { utcTime UTCTime, generalizedTime GeneralizedTime }`,
+        /// as used in `Validity`, converted to Unix seconds.
+        pub fn read_time(&mut self) -> Result<u64, TLSError> {
+            let (tag, value) = self.read_tlv()?;
+            match tag {
+                UTC_TIME => utc_time_to_unix_seconds(value),
+                GENERALIZED_TIME => generalized_time_to_unix_seconds(value),
+                _ => Err(Self::err()),
+            }
+        }
+    }
+
+    /// `UTCTime` in the `YYMMDDHHMMSSZ` form used by `Validity`, converted
+    /// to Unix seconds using the RFC 5280 pivot: `YY >= 50` means 19YY,
+    /// otherwise 20YY.
+    pub fn utc_time_to_unix_seconds(raw: &[u8]) -> Result<u64, TLSError> {
+        if raw.len() != 13 || !raw.is_ascii() {
+            return Err(Reader::err());
+        }
+
+        let s = std::str::from_utf8(raw).map_err(|_| Reader::err())?;
+        if !s.ends_with('Z') {
+            return Err(Reader::err());
+        }
+
+        let field = |range: std::ops::Range<usize>| -> Result<u64, TLSError> {
+            s[range].parse::<u64>().map_err(|_| Reader::err())
+        };
+
+        let yy = field(0..2)?;
+        let year = if yy >= 50 { 1900 + yy } else { 2000 + yy };
+        let month = field(2..4)?;
+        let day = field(4..6)?;
+        let hour = field(6..8)?;
+        let minute = field(8..10)?;
+        let second = field(10..12)?;
+
+        days_from_civil(year, month, day)?
+            .checked_mul(86400)
+            .and_then(|d| d.checked_add(hour * 3600 + minute * 60 + second))
+            .ok_or_else(Reader::err)
+    }
+
+    /// `GeneralizedTime` in the `YYYYMMDDHHMMSSZ` form used by OCSP, converted
+    /// to Unix seconds.  Does not attempt to handle fractional seconds or
+    /// explicit time-zone offsets, which OCSP responders do not emit.
+    pub fn generalized_time_to_unix_seconds(raw: &[u8]) -> Result<u64, TLSError> {
+        if raw.len() != 15 || !raw.is_ascii() {
+            return Err(Reader::err());
+        }
+
+        let s = std::str::from_utf8(raw).map_err(|_| Reader::err())?;
+        if !s.ends_with('Z') {
+            return Err(Reader::err());
+        }
+
+        let field = |range: std::ops::Range<usize>| -> Result<u64, TLSError> {
+            s[range].parse::<u64>().map_err(|_| Reader::err())
+        };
+
+        let year = field(0..4)?;
+        let month = field(4..6)?;
+        let day = field(6..8)?;
+        let hour = field(8..10)?;
+        let minute = field(10..12)?;
+        let second = field(12..14)?;
+
+        days_from_civil(year, month, day)?
+            .checked_mul(86400)
+            .and_then(|d| d.checked_add(hour * 3600 + minute * 60 + second))
+            .ok_or_else(Reader::err)
+    }
+
+    /// Howard Hinnant's days-from-civil algorithm, used here instead of
+    /// pulling in a full calendar/time crate just to turn a handful of
+    /// OCSP timestamps into Unix seconds.
+    ///
+    /// `month`/`day` come straight from attacker-controlled `GeneralizedTime`/
+    /// `UTCTime` fields, so they're range-checked before use: in particular
+    /// `year == 0000` together with `month <= 2` would otherwise underflow
+    /// the `y - 1` below and panic (a DoS reachable from a stapled OCSP
+    /// response or a certificate's own validity field).  The subsequent
+    /// arithmetic is all checked too, so any other input this algorithm
+    /// can't represent is rejected rather than wrapping.
+    fn days_from_civil(y: u64, m: u64, d: u64) -> Result<u64, TLSError> {
+        if m < 1 || m > 12 || d < 1 || d > 31 {
+            return Err(Reader::err());
+        }
+
+        let y = if m <= 2 { y.checked_sub(1).ok_or_else(Reader::err)? } else { y };
+        let era = y / 400;
+        let yoe = y - era * 400;
+        let mp = (m + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + d - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+        era.checked_mul(146097)
+            .and_then(|x| x.checked_add(doe))
+            .and_then(|x| x.checked_sub(719468))
+            .ok_or_else(Reader::err)
+    }
+}
+
+fn oid_to_signature_algorithm(oid: &[u8]) -> Result<&'static webpki::SignatureAlgorithm, TLSError> {
+    match oid {
+        OID_SHA256_WITH_RSA => Ok(&webpki::RSA_PKCS1_2048_8192_SHA256),
+        OID_SHA384_WITH_RSA => Ok(&webpki::RSA_PKCS1_2048_8192_SHA384),
+        OID_SHA512_WITH_RSA => Ok(&webpki::RSA_PKCS1_2048_8192_SHA512),
+        OID_ECDSA_WITH_SHA256 => Ok(&webpki::ECDSA_P256_SHA256),
+        OID_ECDSA_WITH_SHA384 => Ok(&webpki::ECDSA_P384_SHA384),
+        _ => Err(TLSError::General("unsupported OCSP signature algorithm".into())),
+    }
+}
+
+fn oid_to_digest_algorithm(oid: &[u8]) -> Result<&'static digest::Algorithm, TLSError> {
+    match oid {
+        OID_SHA1 => Ok(&digest::SHA1_FOR_LEGACY_USE_ONLY),
+        OID_SHA256 => Ok(&digest::SHA256),
+        OID_SHA384 => Ok(&digest::SHA384),
+        _ => Err(TLSError::General("unsupported OCSP hash algorithm".into())),
+    }
+}
+
+/// `id-pkix-ocsp-basic`, the only `responseType` this module knows how to
+/// parse the `response` octet string of.
+const OID_PKIX_OCSP_BASIC: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+
+const OID_SHA1: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+const OID_SHA256_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+const OID_SHA384_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+const OID_SHA512_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_ECDSA_WITH_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+#[cfg(test)]
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+#[cfg(test)]
+const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify::test_der::*;
+
+    /// Build a minimal v3 DER `Certificate` with the given serial number and
+    /// subject public key, valid over `[not_before, not_after]`
+    /// (`GeneralizedTime` strings, e.g. `"20200101000000Z"`).  It carries a
+    /// version and an extensions field because `verify_signature` parses
+    /// the issuer with webpki, which accepts only v3 certificates with
+    /// extensions.
+    fn build_cert(serial: &[u8], public_key: &[u8], not_before: &str, not_after: &str) -> Vec<u8> {
+        let spki = seq(&[
+            &seq(&[&oid(OID_EC_PUBLIC_KEY), &oid(OID_PRIME256V1)]),
+            &bit_string(public_key),
+        ]);
+        let tbs = seq(&[
+            &explicit(0, &integer(&[0x02])), // version: v3
+            &integer(serial),
+            &seq(&[&oid(OID_ECDSA_WITH_SHA256)]),
+            &empty_name(),
+            &seq(&[&generalized_time(not_before), &generalized_time(not_after)]),
+            &empty_name(),
+            &spki,
+            &explicit(3, &seq(&[&dns_name_san("issuer.example")])),
+        ]);
+        seq(&[
+            &tbs,
+            &seq(&[&oid(OID_ECDSA_WITH_SHA256)]),
+            &bit_string(&[0u8; 8]), // outer cert signature is never checked by this module
+        ])
+    }
+
+    /// Build a signed `BasicOCSPResponse` (wrapped in the outer `OCSPResponse`)
+    /// vouching for `cert_id` with `status` (`0x80` good, `0xa1` revoked,
+    /// `0x82` unknown -- the real wire tags: `good`/`unknown` are IMPLICIT
+    /// NULL/UnknownInfo and so primitive, only `revoked` is constructed),
+    /// fresh as of `[this_update, next_update]`.
+    fn build_response(key: &TestKey,
+                       issuer_name_hash: &[u8],
+                       issuer_key_hash: &[u8],
+                       serial: &[u8],
+                       status_tag: u8,
+                       this_update: &str,
+                       next_update: Option<&str>) -> Vec<u8> {
+        let cert_id = seq(&[
+            &seq(&[&oid(OID_SHA256)]),
+            &octet_string(issuer_name_hash),
+            &octet_string(issuer_key_hash),
+            &integer(serial),
+        ]);
+
+        let mut single_response = vec![cert_id, tlv(status_tag, &[]), generalized_time(this_update)];
+        if let Some(next_update) = next_update {
+            single_response.push(explicit(0, &generalized_time(next_update)));
+        }
+        let single_response = seq(&single_response.iter().map(Vec::as_slice).collect::<Vec<_>>());
+
+        let responder_id = tlv(0xa1, &[]); // [1] byName, content unused (only skipped over)
+        let produced_at = generalized_time(this_update);
+        let responses = seq(&[&single_response]);
+        let tbs_response_data = seq(&[&responder_id, &produced_at, &responses]);
+
+        let signature = sign(key, &tbs_response_data);
+        let basic_response = seq(&[
+            &tbs_response_data,
+            &seq(&[&oid(OID_ECDSA_WITH_SHA256)]),
+            &bit_string(&signature),
+        ]);
+
+        let response_bytes = seq(&[
+            &oid(OID_PKIX_OCSP_BASIC),
+            &octet_string(&basic_response),
+        ]);
+        seq(&[&tlv(0x0a, &[0]), &explicit(0, &response_bytes)])
+    }
+
+    /// Like `build_response`, but with `response_type` in place of
+    /// `id-pkix-ocsp-basic`, for exercising responders that claim some
+    /// other (unsupported) response format.
+    fn build_response_with_type(key: &TestKey,
+                                issuer_name_hash: &[u8],
+                                issuer_key_hash: &[u8],
+                                serial: &[u8],
+                                status_tag: u8,
+                                this_update: &str,
+                                response_type: &[u8]) -> Vec<u8> {
+        let cert_id = seq(&[
+            &seq(&[&oid(OID_SHA256)]),
+            &octet_string(issuer_name_hash),
+            &octet_string(issuer_key_hash),
+            &integer(serial),
+        ]);
+        let single_response = seq(&[&cert_id, &tlv(status_tag, &[]), &generalized_time(this_update)]);
+
+        let responder_id = tlv(0xa1, &[]);
+        let produced_at = generalized_time(this_update);
+        let responses = seq(&[&single_response]);
+        let tbs_response_data = seq(&[&responder_id, &produced_at, &responses]);
+
+        let signature = sign(key, &tbs_response_data);
+        let basic_response = seq(&[
+            &tbs_response_data,
+            &seq(&[&oid(OID_ECDSA_WITH_SHA256)]),
+            &bit_string(&signature),
+        ]);
+
+        let response_bytes = seq(&[
+            &oid(response_type),
+            &octet_string(&basic_response),
+        ]);
+        seq(&[&tlv(0x0a, &[0]), &explicit(0, &response_bytes)])
+    }
+
+    /// A matched issuer/end-entity pair, plus the hashes a correctly-targeted
+    /// response must carry.
+    ///
+    /// `verify_signature` checks a stapled response directly against the
+    /// issuer certificate's own key (this module has no notion of a
+    /// delegated OCSP responder certificate), so the response must be
+    /// signed by `issuer`'s key, not a separate responder key.
+    struct Fixture {
+        issuer: Certificate,
+        ee: Certificate,
+        issuer_key: TestKey,
+        issuer_name_hash: Vec<u8>,
+        issuer_key_hash: Vec<u8>,
+        serial: Vec<u8>,
+    }
+
+    fn fixture() -> Fixture {
+        let issuer_key = generate_key();
+        let serial = vec![0x01, 0x02, 0x03];
+
+        let issuer_der = build_cert(&[0x10], &issuer_key.public_key,
+                                    "20200101000000Z", "20300101000000Z");
+        let ee_der = build_cert(&serial, &generate_key().public_key,
+                                "20200101000000Z", "20300101000000Z");
+
+        let issuer_name_hash = digest::digest(&digest::SHA256, &subject_der(&issuer_der).unwrap()).as_ref().to_vec();
+        let issuer_key_hash = digest::digest(&digest::SHA256, &spki_bits(&issuer_der).unwrap()).as_ref().to_vec();
+
+        Fixture {
+            issuer: Certificate(issuer_der),
+            ee: Certificate(ee_der),
+            issuer_key,
+            issuer_name_hash,
+            issuer_key_hash,
+            serial,
+        }
+    }
+
+    const FRESH: &str = "20200601000000Z";
+    const EXPIRED_UPDATE: &str = "20200601000000Z";
+    const EXPIRED_NEXT: &str = "20200602000000Z";
+    const NOW_PAST_EXPIRY: u64 = 1_600_000_000; // well after EXPIRED_NEXT
+
+    fn now_for(s: &str) -> u64 {
+        // `s` is one of the `GeneralizedTime` constants above; reuse the
+        // module's own parser so the test stays in lock-step with it.
+        der::generalized_time_to_unix_seconds(s.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn good_response_is_ok_under_every_policy() {
+        let f = fixture();
+        let response = build_response(&f.issuer_key, &f.issuer_name_hash, &f.issuer_key_hash,
+                                      &f.serial, 0x80, FRESH, None);
+        let now = now_for(FRESH);
+        for policy in [RevocationPolicy::Ignore, RevocationPolicy::SoftFail, RevocationPolicy::HardFail] {
+            assert!(verify(&f.ee, &f.issuer, &response, policy, now).is_ok());
+        }
+    }
+
+    #[test]
+    fn revoked_response_is_rejected_unless_ignored() {
+        let f = fixture();
+        let response = build_response(&f.issuer_key, &f.issuer_name_hash, &f.issuer_key_hash,
+                                      &f.serial, 0xa1, FRESH, None);
+        let now = now_for(FRESH);
+
+        assert!(verify(&f.ee, &f.issuer, &response, RevocationPolicy::Ignore, now).is_ok());
+        for policy in [RevocationPolicy::SoftFail, RevocationPolicy::HardFail] {
+            assert!(matches!(verify(&f.ee, &f.issuer, &response, policy, now),
+                             Err(TLSError::CertificateRevoked)));
+        }
+    }
+
+    #[test]
+    fn unknown_response_is_rejected_only_under_hard_fail() {
+        let f = fixture();
+        let response = build_response(&f.issuer_key, &f.issuer_name_hash, &f.issuer_key_hash,
+                                      &f.serial, 0x82, FRESH, None);
+        let now = now_for(FRESH);
+
+        assert!(verify(&f.ee, &f.issuer, &response, RevocationPolicy::Ignore, now).is_ok());
+        assert!(verify(&f.ee, &f.issuer, &response, RevocationPolicy::SoftFail, now).is_ok());
+        assert!(matches!(verify(&f.ee, &f.issuer, &response, RevocationPolicy::HardFail, now),
+                         Err(TLSError::CertificateRevoked)));
+    }
+
+    #[test]
+    fn expired_response_is_rejected_only_under_hard_fail() {
+        let f = fixture();
+        let response = build_response(&f.issuer_key, &f.issuer_name_hash, &f.issuer_key_hash,
+                                      &f.serial, 0x80, EXPIRED_UPDATE, Some(EXPIRED_NEXT));
+
+        assert!(verify(&f.ee, &f.issuer, &response, RevocationPolicy::Ignore, NOW_PAST_EXPIRY).is_ok());
+        assert!(verify(&f.ee, &f.issuer, &response, RevocationPolicy::SoftFail, NOW_PAST_EXPIRY).is_ok());
+        assert!(verify(&f.ee, &f.issuer, &response, RevocationPolicy::HardFail, NOW_PAST_EXPIRY).is_err());
+    }
+
+    #[test]
+    fn cert_id_mismatch_is_rejected_only_under_hard_fail() {
+        let f = fixture();
+        let wrong_serial = vec![0xff, 0xff];
+        let response = build_response(&f.issuer_key, &f.issuer_name_hash, &f.issuer_key_hash,
+                                      &wrong_serial, 0x80, FRESH, None);
+        let now = now_for(FRESH);
+
+        assert!(verify(&f.ee, &f.issuer, &response, RevocationPolicy::Ignore, now).is_ok());
+        assert!(verify(&f.ee, &f.issuer, &response, RevocationPolicy::SoftFail, now).is_ok());
+        assert!(verify(&f.ee, &f.issuer, &response, RevocationPolicy::HardFail, now).is_err());
+    }
+
+    #[test]
+    fn truncated_response_is_rejected_only_under_hard_fail() {
+        let f = fixture();
+        let response = build_response(&f.issuer_key, &f.issuer_name_hash, &f.issuer_key_hash,
+                                      &f.serial, 0x80, FRESH, None);
+        let truncated = &response[..response.len() / 2];
+        let now = now_for(FRESH);
+
+        assert!(verify(&f.ee, &f.issuer, truncated, RevocationPolicy::Ignore, now).is_ok());
+        assert!(verify(&f.ee, &f.issuer, truncated, RevocationPolicy::SoftFail, now).is_ok());
+        assert!(verify(&f.ee, &f.issuer, truncated, RevocationPolicy::HardFail, now).is_err());
+    }
+
+    #[test]
+    fn response_with_an_unrecognized_response_type_is_rejected() {
+        let f = fixture();
+        // id-pkix-ocsp-nonce, not id-pkix-ocsp-basic: a well-formed
+        // ResponseBytes this module has no business treating as a
+        // BasicOCSPResponse.
+        let other_oid = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x02];
+        let response = build_response_with_type(&f.issuer_key, &f.issuer_name_hash, &f.issuer_key_hash,
+                                                &f.serial, 0x80, FRESH, other_oid);
+        let now = now_for(FRESH);
+
+        assert!(matches!(verify(&f.ee, &f.issuer, &response, RevocationPolicy::HardFail, now),
+                         Err(TLSError::General(_))));
+    }
+
+    #[test]
+    fn empty_response_is_treated_like_a_missing_response() {
+        let f = fixture();
+        assert!(verify(&f.ee, &f.issuer, &[], RevocationPolicy::Ignore, 0).is_ok());
+        assert!(verify(&f.ee, &f.issuer, &[], RevocationPolicy::SoftFail, 0).is_ok());
+        assert!(matches!(verify(&f.ee, &f.issuer, &[], RevocationPolicy::HardFail, 0),
+                         Err(TLSError::CertificateRevoked)));
+    }
+
+    #[test]
+    fn generalized_time_rejects_non_ascii_without_panicking() {
+        // 15 bytes, valid UTF-8, ends in 'Z', but a multi-byte sequence
+        // straddles a would-be `str` slice boundary -- this must not panic.
+        let raw = [0x30, 0x31, 0x32, 0xC2, 0xA0, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x30, 0x31, b'Z'];
+        assert!(der::generalized_time_to_unix_seconds(&raw).is_err());
+    }
+
+    #[test]
+    fn generalized_time_parses_a_well_formed_value() {
+        assert_eq!(der::generalized_time_to_unix_seconds(b"20200101000000Z").unwrap(), 1_577_836_800);
+    }
+
+    #[test]
+    fn utc_time_applies_the_rfc_5280_century_pivot() {
+        assert_eq!(der::utc_time_to_unix_seconds(b"500101000000Z").unwrap(),
+                  der::generalized_time_to_unix_seconds(b"19500101000000Z").unwrap());
+        assert_eq!(der::utc_time_to_unix_seconds(b"490101000000Z").unwrap(),
+                  der::generalized_time_to_unix_seconds(b"20490101000000Z").unwrap());
+    }
+
+    #[test]
+    fn time_parsers_reject_truncated_input() {
+        assert!(der::generalized_time_to_unix_seconds(b"2020010100").is_err());
+        assert!(der::utc_time_to_unix_seconds(b"500101").is_err());
+    }
+
+    #[test]
+    fn generalized_time_rejects_year_zero_without_panicking() {
+        // Legal as far as the field-level GeneralizedTime grammar is
+        // concerned (4 digit year, well-formed otherwise), but "0000" with
+        // month <= 2 used to underflow `days_from_civil`'s `y - 1` and
+        // panic -- a DoS reachable from an attacker-controlled OCSP
+        // response or certificate validity field.
+        assert!(der::generalized_time_to_unix_seconds(b"00000101000000Z").is_err());
+    }
+
+    #[test]
+    fn generalized_time_rejects_out_of_range_month_and_day() {
+        assert!(der::generalized_time_to_unix_seconds(b"20201301000000Z").is_err());
+        assert!(der::generalized_time_to_unix_seconds(b"20200132000000Z").is_err());
+        assert!(der::generalized_time_to_unix_seconds(b"20200100000000Z").is_err());
+    }
+}
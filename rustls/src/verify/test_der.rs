@@ -0,0 +1,65 @@
+//! Minimal DER-construction helpers shared by the test fixtures in
+//! `verify` and `verify::ocsp`: just enough to build the certificates and
+//! OCSP responses those modules' parsers consume, not a general-purpose
+//! ASN.1 encoder.
+
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+
+pub(crate) fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = value.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else if len <= 0xff {
+        out.push(0x81);
+        out.push(len as u8);
+    } else {
+        out.push(0x82);
+        out.push((len >> 8) as u8);
+        out.push((len & 0xff) as u8);
+    }
+    out.extend_from_slice(value);
+    out
+}
+
+pub(crate) fn seq(parts: &[&[u8]]) -> Vec<u8> {
+    tlv(0x30, &parts.concat())
+}
+
+pub(crate) fn oid(bytes: &[u8]) -> Vec<u8> { tlv(0x06, bytes) }
+pub(crate) fn octet_string(bytes: &[u8]) -> Vec<u8> { tlv(0x04, bytes) }
+pub(crate) fn integer(bytes: &[u8]) -> Vec<u8> { tlv(0x02, bytes) }
+pub(crate) fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    let mut value = vec![0x00]; // no unused bits
+    value.extend_from_slice(bytes);
+    tlv(0x03, &value)
+}
+pub(crate) fn generalized_time(s: &str) -> Vec<u8> { tlv(0x18, s.as_bytes()) }
+pub(crate) fn explicit(n: u8, inner: &[u8]) -> Vec<u8> { tlv(0xa0 | n, inner) }
+pub(crate) fn dns_name_san(name: &str) -> Vec<u8> {
+    let general_names = seq(&[&tlv(0x82, name.as_bytes())]);
+    seq(&[&oid(&[0x55, 0x1d, 0x11]), &octet_string(&general_names)])
+}
+
+/// An empty `Name` -- self-signed certs need `issuer == subject`, which
+/// an empty one trivially satisfies; callers never inspect DNs.
+pub(crate) fn empty_name() -> Vec<u8> { seq(&[]) }
+
+pub(crate) struct TestKey {
+    key_pair: EcdsaKeyPair,
+    pub(crate) public_key: Vec<u8>,
+}
+
+pub(crate) fn generate_key() -> TestKey {
+    let rng = SystemRandom::new();
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+    let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref()).unwrap();
+    let public_key = key_pair.public_key().as_ref().to_vec();
+    TestKey { key_pair, public_key }
+}
+
+pub(crate) fn sign(key: &TestKey, msg: &[u8]) -> Vec<u8> {
+    let rng = SystemRandom::new();
+    key.key_pair.sign(&rng, msg).unwrap().as_ref().to_vec()
+}